@@ -1,192 +1,137 @@
 //! This crate allows you to permanently set environment variables
 //!
+//! Rather than appending a new line to your shell config on every call
+//! (which slowly accumulates duplicate and stale assignments), `env_perm`
+//! keeps a single managed file per shell, written in that shell's own
+//! syntax, and makes sure the shell's rc file sources it exactly once.
+//! Setting the same variable twice simply rewrites its entry instead of
+//! adding another line. Bash, Zsh, fish, Nushell and PowerShell are all
+//! supported; on Windows, variables are persisted straight to the
+//! registry instead.
+//!
+//! `set`/`check_or_set` quote plain values for you, so there's no need to
+//! hand-escape anything. Wrap a value in [`Raw`] if it should be written
+//! verbatim instead, e.g. so it can still expand `$HOME` when sourced.
+//!
 //! # Examples
-//! ```rust
+//! ```rust,no_run
+//! // This example mutates the current user's real shell rc files, so it's
+//! // `no_run`: only type-checked, never executed, by `cargo test`.
 //! // Check if DUMMY is set, if not set it to 1
-//! // export DUMMY=1
 //! env_perm::check_or_set("DUMMY", 1).expect("Failed to find or set DUMMY");
 //! // Append $HOME/some/cool/bin to $PATH
-//! // export PATH= "$HOME/some/cool/bin:$PATH"
 //! env_perm::append("PATH", "$HOME/some/cool/bin").expect("Couldn't find PATH");
 //! // Sets a variable without checking if it exists.
-//! // Note you need to use a raw string literal to include ""
-//! // export DUMMY="/something"
-//! env_perm::set("DUMMY", r#""/something""#).expect("Failed to set DUMMY");
+//! env_perm::set("DUMMY", "/something").expect("Failed to set DUMMY");
+//! // Sets a variable to a literal shell expression instead of a plain value.
+//! env_perm::set("JAVA_HOME", env_perm::Raw("$HOME/.sdkman/candidates/java/current"))
+//!     .expect("Failed to set JAVA_HOME");
 //! ```
 
-use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::{env, fmt, io};
 
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::{env, fs};
-use std::{fmt, io};
+#[cfg(target_family = "unix")]
+mod shell;
 
-use boolinator::Boolinator;
-use phf::phf_map;
+#[cfg(target_family = "unix")]
+mod unix;
 
-#[derive(Debug, PartialEq)]
-enum ShellBin {
-    Zsh,
-    Bash,
+#[cfg(target_family = "windows")]
+mod windows;
 
-    NotSupported,
+/// A value to persist with [`set`] or [`check_or_set`]. Implemented for
+/// every `Display` type, which is quoted as a shell literal by
+/// construction, and for [`Raw`], which is spliced in verbatim.
+pub trait IntoShellValue {
+    #[doc(hidden)]
+    fn into_shell_value(self) -> ShellValue;
 }
 
-impl FromStr for ShellBin {
-    type Err = ShellBin;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.to_uppercase();
-        let s = s.as_str();
+/// A value and whether it should be quoted before being persisted.
+#[doc(hidden)]
+pub struct ShellValue {
+    text: String,
+    #[cfg_attr(target_family = "windows", allow(dead_code))]
+    raw: bool,
+}
 
-        match s {
-            "ZSH" => Ok(ShellBin::Zsh),
-            "BASH" => Ok(ShellBin::Bash),
-            _ => Err(ShellBin::NotSupported),
+impl<T: fmt::Display> IntoShellValue for T {
+    fn into_shell_value(self) -> ShellValue {
+        ShellValue {
+            text: self.to_string(),
+            raw: false,
         }
     }
 }
 
-static SHELL: [ShellProfile; 2] = [
-    ShellProfile {
-        shell_bin: ShellBin::Zsh,
-        shell_cfg_files: phf_map! {
-            "profile" => Cow::Borrowed(".zprofile"),
-            "login" => Cow::Borrowed(".zlogin"),
-            "shellrc" => Cow::Borrowed(".zshrc"),
-        },
-    },
-    ShellProfile {
-        shell_bin: ShellBin::Bash,
-        shell_cfg_files: phf_map! {
-            "profile" => Cow::Borrowed(".bash_profile"),
-            "login" => Cow::Borrowed(".bash_login"),
-            "shellrc" => Cow::Borrowed(".bashrc"),
-        },
-    },
-];
-
-#[derive(Debug)]
-struct ShellProfile {
-    shell_bin: ShellBin,
-    shell_cfg_files: phf::Map<&'static str, Cow<'static, str>>,
+/// Wraps a value so `set`/`check_or_set` write it verbatim instead of
+/// quoting it as a literal, e.g. because it's meant to expand a shell
+/// variable like `$HOME` when the managed file is sourced.
+pub struct Raw<T>(pub T);
+
+impl<T: fmt::Display> IntoShellValue for Raw<T> {
+    fn into_shell_value(self) -> ShellValue {
+        ShellValue {
+            text: self.0.to_string(),
+            raw: true,
+        }
+    }
 }
 
 /// Checks if a environment variable is set.
 /// If it is then nothing will happen.
 /// If it's not then it will be added
 /// to your profile.
-pub fn check_or_set<T, U>(var: T, value: U) -> io::Result<()>
+pub fn check_or_set<T, V>(var: T, value: V) -> io::Result<()>
 where
     T: fmt::Display + AsRef<std::ffi::OsStr>,
-    U: fmt::Display,
+    V: IntoShellValue,
 {
     env::var(&var).map(|_| ()).or_else(|_| set(var, value))
 }
 
-/// Appends a value to an environment variable
-/// Useful for appending a value to PATH
+/// Appends a value to an environment variable.
+/// Useful for appending a value to PATH.
+///
+/// Re-running `append` with the same value is a no-op: the persisted
+/// entry is only updated when `value` isn't already one of its segments.
+/// Unlike `set`, `value` is always spliced in verbatim, since `append` is
+/// meant for path-like segments that may themselves expand a variable
+/// such as `$HOME`.
 pub fn append<T: fmt::Display>(var: T, value: T) -> io::Result<()> {
-    let mut profile = get_profile()?;
-    writeln!(profile, "\nexport {}=\"{}:${}\"", var, value, var)?;
-    profile.flush()
+    #[cfg(target_family = "unix")]
+    return unix::append(&var.to_string(), &value.to_string());
+
+    #[cfg(target_family = "windows")]
+    return windows::append(&var.to_string(), &value.to_string());
 }
 
 /// Sets an environment variable without checking
 /// if it exists.
-/// If it does you will end up with two
-/// assignments in your profile.
+/// If it does, its persisted entry is simply overwritten, so calling
+/// `set` repeatedly with the same `var` is idempotent.
 /// It's recommended to use `check_or_set`
 /// unless you are certain it doesn't exist.
-pub fn set<T: fmt::Display, U: fmt::Display>(var: T, value: U) -> io::Result<()> {
-    let mut profile = get_profile()?;
-    writeln!(profile, "\nexport {}={}", var, value)?;
-    profile.flush()
-}
+///
+/// `value` is quoted as a shell literal by construction; wrap it in
+/// [`Raw`] if it should be written verbatim instead.
+pub fn set<T: fmt::Display, V: IntoShellValue>(var: T, value: V) -> io::Result<()> {
+    let value = value.into_shell_value();
+
+    #[cfg(target_family = "unix")]
+    return unix::set(&var.to_string(), &value.text, value.raw);
 
-fn get_profile() -> io::Result<File> {
-    let shell_bin = env::var("SHELL").expect("SHELL environment variable was not found");
-    let mut shell_bin = shell_bin.as_str();
-
-    dirs::home_dir()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))
-        .and_then(|hd| {
-            hd.clone()
-                .as_path()
-                .to_str()
-                .ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        "Failed to coerce Home directory as a valid Path",
-                    )
-                })
-                .and_then(|profile| {
-                    shell_bin = shell_bin
-                        .split('/')
-                        .last()
-                        .expect("Unable to parse shell path in environment variables");
-
-                    fs::metadata(profile)
-                        .map_err(|_| {
-                            io::Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "Path to profile was invalid, or was not found",
-                            )
-                        })
-                        .and_then(|md| {
-                            let readonly = !md.permissions().readonly();
-                            readonly.as_result(
-                                md,
-                                io::Error::new(
-                                    io::ErrorKind::PermissionDenied,
-                                    "Unable to write to home directory, cannot export env var",
-                                ),
-                            )
-                        })
-                        .map(|_| profile)
-                })
-                .map(PathBuf::from)
-        })
-        .and_then(|path| find_profile(path, shell_bin))
+    #[cfg(target_family = "windows")]
+    return windows::set(&var.to_string(), &value.text);
 }
 
-#[cfg(target_family = "unix")]
-fn find_profile(mut profile: PathBuf, shell_bin: &str) -> io::Result<File> {
-    let mut open_opts = std::fs::OpenOptions::new();
-    open_opts.append(true).create(false);
-
-    if let Some(sp) = SHELL.iter().find(|sp| {
-        sp.shell_bin
-            == ShellBin::from_str(shell_bin)
-                .expect("Unable to match shell_bin with a supported ShellType")
-    }) {
-        let entries = sp.shell_cfg_files.entries();
-        for (k, v) in entries {
-            if !v.is_empty() {
-                if k == &"profile" {
-                    open_opts.create(true);
-                }
-
-                profile.push(v.as_ref());
-
-                return match open_opts.open(profile.clone()) {
-                    Ok(f) => {
-                        println!("Selected: {}", profile.display());
-                        Ok(f)
-                    }
-                    Err(_) => {
-                        profile.pop();
-                        continue;
-                    }
-                };
-            }
-        }
-    }
+/// Removes a variable previously persisted by `set`, `check_or_set`,
+/// or `append`. It's not an error to `unset` a variable that was never set.
+pub fn unset<T: fmt::Display + AsRef<OsStr>>(var: T) -> io::Result<()> {
+    #[cfg(target_family = "unix")]
+    return unix::unset(&var.to_string());
 
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        "No shell profiles were found",
-    ))
+    #[cfg(target_family = "windows")]
+    return windows::unset(&var.to_string());
 }
@@ -0,0 +1,482 @@
+//! Per-shell syntax for persisting environment variables.
+//!
+//! Every supported shell gets its own [`Shell`] implementation so the lines
+//! `env_perm` writes are syntactically correct for that shell, rather than
+//! POSIX `export` syntax being written into a fish or Nushell config.
+
+use std::io;
+
+/// Knows how to read and write a particular shell's variable-assignment
+/// syntax, and where that shell expects its config to live.
+pub(crate) trait Shell {
+    /// Candidate rc files, relative to `$HOME`, tried in order. The first
+    /// one that already exists is used; if none exist, the first in the
+    /// list is created.
+    fn config_files(&self) -> &'static [&'static str];
+
+    /// Name of the managed file (relative to `$HOME`) that holds every
+    /// variable `env_perm` has set for this shell.
+    fn managed_file_name(&self) -> &'static str;
+
+    /// The line an rc file needs so it picks up the managed file.
+    fn source_line(&self) -> String;
+
+    /// How this shell refers to one of its own already-set variables,
+    /// e.g. `$PATH` or `$env.PATH`. Used as the starting point for `append`
+    /// when nothing has been persisted for `var` yet.
+    fn variable_reference(&self, var: &str) -> String;
+
+    /// The line that assigns `var` to `value` in this shell's syntax.
+    fn format_set(&self, var: &str, value: &str) -> String;
+
+    /// The line that appends `value` to `var`'s existing value (`existing`,
+    /// either the [`Shell::rhs`] of a previous managed entry or
+    /// [`Shell::variable_reference`]).
+    fn format_append(&self, var: &str, value: &str, existing: &str) -> String;
+
+    /// Whether a repeated [`Shell::format_append`] line for `var` is safe to
+    /// keep as an independent line alongside earlier ones, rather than
+    /// having to be merged into a single assignment. True for shells whose
+    /// append idiom for `var` already folds in whatever the variable
+    /// resolves to at the time the line runs (e.g. fish's `fish_add_path`,
+    /// or Nushell's `$env.PATH | prepend`), so stacking several such lines
+    /// composes correctly instead of each one clobbering the last.
+    fn append_is_composable(&self, var: &str) -> bool {
+        let _ = var;
+        false
+    }
+
+    /// Recovers the variable name a line previously written by
+    /// [`Shell::format_set`] or [`Shell::format_append`] assigns to.
+    fn parse_var(&self, line: &str) -> Option<String>;
+
+    /// Recovers the raw, unprocessed right-hand side of a line previously
+    /// written by [`Shell::format_set`] or [`Shell::format_append`], for use
+    /// as `existing` in a subsequent `format_append` call. It's returned
+    /// exactly as written, never quote-stripped or otherwise
+    /// reinterpreted, so chaining through it can't lose or corrupt
+    /// whatever a previous `format_append` embedded in it.
+    fn rhs<'a>(&self, line: &'a str) -> Option<&'a str>;
+
+    /// Quotes `value` as a literal in this shell's syntax, so it's safe to
+    /// splice straight into [`Shell::format_set`] regardless of what
+    /// characters it contains. Rejects embedded newlines, which none of
+    /// these quoting forms can represent on a single line.
+    fn quote(&self, value: &str) -> io::Result<String>;
+}
+
+/// Rejects embedded newlines, which none of the quoting forms below (nor
+/// the raw/verbatim path in `unix::set`/`unix::append`) can represent on a
+/// single managed-file line without corrupting the one-entry-per-line
+/// invariant `read_entries` relies on.
+pub(crate) fn reject_newline(value: &str) -> io::Result<()> {
+    if value.contains('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "value must not contain a newline",
+        ));
+    }
+    Ok(())
+}
+
+/// Zsh and Bash, and anything else speaking POSIX `export` syntax.
+pub(crate) struct Posix {
+    pub(crate) config_files: &'static [&'static str],
+    pub(crate) managed_file_name: &'static str,
+}
+
+impl Shell for Posix {
+    fn config_files(&self) -> &'static [&'static str] {
+        self.config_files
+    }
+
+    fn managed_file_name(&self) -> &'static str {
+        self.managed_file_name
+    }
+
+    fn source_line(&self) -> String {
+        format!(
+            "[ -f \"$HOME/{name}\" ] && . \"$HOME/{name}\"",
+            name = self.managed_file_name
+        )
+    }
+
+    fn variable_reference(&self, var: &str) -> String {
+        format!("${}", var)
+    }
+
+    fn format_set(&self, var: &str, value: &str) -> String {
+        format!("export {}={}", var, value)
+    }
+
+    fn format_append(&self, var: &str, value: &str, existing: &str) -> String {
+        self.format_set(var, &format!("{}:{}", value, existing))
+    }
+
+    fn parse_var(&self, line: &str) -> Option<String> {
+        let rest = line.trim().strip_prefix("export ")?;
+        let (name, _) = rest.split_once('=')?;
+        Some(name.to_string())
+    }
+
+    fn rhs<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let rest = line.trim().strip_prefix("export ")?;
+        let (_, value) = rest.split_once('=')?;
+        Some(value)
+    }
+
+    fn quote(&self, value: &str) -> io::Result<String> {
+        reject_newline(value)?;
+        Ok(format!("'{}'", value.replace('\'', r"'\''")))
+    }
+}
+
+/// Fish, whose `set -Ux` persists a universal variable and whose `PATH`
+/// is conventionally managed through `fish_add_path`.
+pub(crate) struct Fish;
+
+impl Shell for Fish {
+    fn config_files(&self) -> &'static [&'static str] {
+        &[".config/fish/config.fish"]
+    }
+
+    fn managed_file_name(&self) -> &'static str {
+        ".env_perm_env.fish"
+    }
+
+    fn source_line(&self) -> String {
+        format!(
+            "if test -f \"$HOME/{name}\"; source \"$HOME/{name}\"; end",
+            name = self.managed_file_name()
+        )
+    }
+
+    fn variable_reference(&self, var: &str) -> String {
+        format!("${}", var)
+    }
+
+    fn format_set(&self, var: &str, value: &str) -> String {
+        format!("set -Ux {} {}", var, value)
+    }
+
+    fn format_append(&self, var: &str, value: &str, existing: &str) -> String {
+        if var == "PATH" {
+            format!("fish_add_path {}", value)
+        } else {
+            self.format_set(var, &format!("{} {}", value, existing))
+        }
+    }
+
+    fn append_is_composable(&self, var: &str) -> bool {
+        var == "PATH"
+    }
+
+    fn parse_var(&self, line: &str) -> Option<String> {
+        let line = line.trim();
+        if line.starts_with("fish_add_path ") {
+            return Some("PATH".to_string());
+        }
+        let rest = line.strip_prefix("set -Ux ")?;
+        let (name, _) = rest.split_once(' ')?;
+        Some(name.to_string())
+    }
+
+    fn rhs<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("fish_add_path ") {
+            return Some(value);
+        }
+        let rest = line.strip_prefix("set -Ux ")?;
+        let (_, value) = rest.split_once(' ')?;
+        Some(value)
+    }
+
+    fn quote(&self, value: &str) -> io::Result<String> {
+        reject_newline(value)?;
+        let escaped = value.replace('\\', "\\\\").replace('\'', "\\'");
+        Ok(format!("'{}'", escaped))
+    }
+}
+
+/// Nushell, which assigns into `$env` and has its own list-append idiom.
+pub(crate) struct Nushell;
+
+impl Shell for Nushell {
+    fn config_files(&self) -> &'static [&'static str] {
+        &[".config/nushell/env.nu"]
+    }
+
+    fn managed_file_name(&self) -> &'static str {
+        ".env_perm_env.nu"
+    }
+
+    fn source_line(&self) -> String {
+        format!("source \"~/{}\"", self.managed_file_name())
+    }
+
+    fn variable_reference(&self, var: &str) -> String {
+        format!("$env.{}", var)
+    }
+
+    fn format_set(&self, var: &str, value: &str) -> String {
+        format!("$env.{} = {}", var, value)
+    }
+
+    fn format_append(&self, var: &str, value: &str, existing: &str) -> String {
+        if var == "PATH" {
+            format!("$env.PATH = ($env.PATH | prepend \"{}\")", value)
+        } else {
+            format!("$env.{} = (\"{}\" | append {})", var, value, existing)
+        }
+    }
+
+    fn append_is_composable(&self, var: &str) -> bool {
+        var == "PATH"
+    }
+
+    fn parse_var(&self, line: &str) -> Option<String> {
+        let rest = line.trim().strip_prefix("$env.")?;
+        let (name, _) = rest.split_once(" = ")?;
+        Some(name.to_string())
+    }
+
+    fn rhs<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let rest = line.trim().strip_prefix("$env.")?;
+        let (_, rhs) = rest.split_once(" = ")?;
+        Some(rhs)
+    }
+
+    fn quote(&self, value: &str) -> io::Result<String> {
+        reject_newline(value)?;
+        let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+        Ok(format!("\"{}\"", escaped))
+    }
+}
+
+/// PowerShell, which assigns into the `env:` drive.
+pub(crate) struct PowerShell;
+
+impl Shell for PowerShell {
+    fn config_files(&self) -> &'static [&'static str] {
+        &[".config/powershell/Microsoft.PowerShell_profile.ps1"]
+    }
+
+    fn managed_file_name(&self) -> &'static str {
+        ".env_perm_env.ps1"
+    }
+
+    fn source_line(&self) -> String {
+        format!(". \"$HOME/{}\"", self.managed_file_name())
+    }
+
+    fn variable_reference(&self, var: &str) -> String {
+        format!("$env:{}", var)
+    }
+
+    fn format_set(&self, var: &str, value: &str) -> String {
+        format!("$env:{} = {}", var, value)
+    }
+
+    fn format_append(&self, var: &str, value: &str, existing: &str) -> String {
+        format!(
+            "$env:{} = '{}' + [IO.Path]::PathSeparator + ({})",
+            var, value, existing
+        )
+    }
+
+    fn parse_var(&self, line: &str) -> Option<String> {
+        let rest = line.trim().strip_prefix("$env:")?;
+        let (name, _) = rest.split_once(" = ")?;
+        Some(name.to_string())
+    }
+
+    fn rhs<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let rest = line.trim().strip_prefix("$env:")?;
+        let (_, rhs) = rest.split_once(" = ")?;
+        Some(rhs)
+    }
+
+    fn quote(&self, value: &str) -> io::Result<String> {
+        reject_newline(value)?;
+        Ok(format!("'{}'", value.replace('\'', "''")))
+    }
+}
+
+/// Picks the [`Shell`] implementation for the basename of `$SHELL`
+/// (e.g. `zsh`, `bash`, `fish`, `nu`, `pwsh`).
+pub(crate) fn detect(shell_bin: &str) -> Option<Box<dyn Shell>> {
+    match shell_bin.to_lowercase().as_str() {
+        "zsh" => Some(Box::new(Posix {
+            config_files: &[".zprofile", ".zlogin", ".zshrc"],
+            managed_file_name: ".env_perm_env.sh",
+        })),
+        "bash" => Some(Box::new(Posix {
+            config_files: &[".bash_profile", ".bash_login", ".bashrc"],
+            managed_file_name: ".env_perm_env.sh",
+        })),
+        "fish" => Some(Box::new(Fish)),
+        "nu" => Some(Box::new(Nushell)),
+        "pwsh" | "powershell" => Some(Box::new(PowerShell)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn posix() -> Posix {
+        Posix {
+            config_files: &[".bashrc"],
+            managed_file_name: ".env_perm_env.sh",
+        }
+    }
+
+    #[test]
+    fn posix_round_trips_through_format_set() {
+        let shell = posix();
+        let line = shell.format_set("PATH", "'/a'");
+        assert_eq!(shell.parse_var(&line), Some("PATH".to_string()));
+        assert_eq!(shell.rhs(&line), Some("'/a'"));
+    }
+
+    #[test]
+    fn posix_quote_escapes_embedded_single_quotes_and_passes_dollar_and_backtick_through() {
+        let shell = posix();
+        let quoted = shell
+            .quote(r#"'; rm -rf ~; echo "$(whoami)" `id` '"#)
+            .unwrap();
+
+        // Single quotes are the only thing POSIX single-quoting can't
+        // represent directly, so they're the only thing escaped; `$`,
+        // backticks and `\` are inert inside single quotes and pass through
+        // literally rather than being interpreted.
+        assert_eq!(quoted, r#"''\''; rm -rf ~; echo "$(whoami)" `id` '\'''"#);
+
+        let line = shell.format_set("DUMMY", &quoted);
+        assert_eq!(shell.rhs(&line), Some(quoted.as_str()));
+    }
+
+    #[test]
+    fn posix_quote_rejects_embedded_newline() {
+        let shell = posix();
+        assert!(shell.quote("one\ntwo").is_err());
+    }
+
+    #[test]
+    fn posix_append_chains_off_rhs_without_losing_segments() {
+        let shell = posix();
+        let first = shell.format_set("PATH", "'/a'");
+        let existing = shell.rhs(&first).unwrap();
+        let second = shell.format_append("PATH", "/b", existing);
+        assert_eq!(shell.parse_var(&second), Some("PATH".to_string()));
+        let rhs = shell.rhs(&second).unwrap();
+        assert!(rhs.contains("/a"));
+        assert!(rhs.contains("/b"));
+    }
+
+    #[test]
+    fn fish_path_append_is_composable_and_not_merged() {
+        let shell = Fish;
+        assert!(shell.append_is_composable("PATH"));
+        assert!(!shell.append_is_composable("JAVA_HOME"));
+
+        let line = shell.format_append("PATH", "/a", "");
+        assert_eq!(line, "fish_add_path /a");
+        assert_eq!(shell.parse_var(&line), Some("PATH".to_string()));
+        assert_eq!(shell.rhs(&line), Some("/a"));
+    }
+
+    #[test]
+    fn fish_quote_escapes_backslashes_and_single_quotes() {
+        let shell = Fish;
+        let payload = r#"'; rm -rf ~; echo "$(whoami)" `id` \ '"#;
+        let quoted = shell.quote(payload).unwrap();
+
+        assert_eq!(quoted, r#"'\'; rm -rf ~; echo "$(whoami)" `id` \\ \''"#);
+
+        let line = shell.format_set("DUMMY", &quoted);
+        assert_eq!(shell.rhs(&line), Some(quoted.as_str()));
+    }
+
+    #[test]
+    fn fish_quote_rejects_embedded_newline() {
+        let shell = Fish;
+        assert!(shell.quote("one\ntwo").is_err());
+    }
+
+    #[test]
+    fn fish_non_path_round_trips_through_format_set() {
+        let shell = Fish;
+        let line = shell.format_set("JAVA_HOME", "'/opt/java'");
+        assert_eq!(shell.parse_var(&line), Some("JAVA_HOME".to_string()));
+        assert_eq!(shell.rhs(&line), Some("'/opt/java'"));
+    }
+
+    #[test]
+    fn nushell_path_append_is_composable_and_not_merged() {
+        let shell = Nushell;
+        assert!(shell.append_is_composable("PATH"));
+        assert!(!shell.append_is_composable("JAVA_HOME"));
+
+        let line = shell.format_append("PATH", "/a", "");
+        assert_eq!(shell.parse_var(&line), Some("PATH".to_string()));
+    }
+
+    #[test]
+    fn nushell_quote_escapes_backslashes_and_double_quotes() {
+        let shell = Nushell;
+        let payload = r#"'; rm -rf ~; echo "$(whoami)" `id` \ '"#;
+        let quoted = shell.quote(payload).unwrap();
+
+        assert_eq!(quoted, r#""'; rm -rf ~; echo \"$(whoami)\" `id` \\ '""#);
+
+        let line = shell.format_set("DUMMY", &quoted);
+        assert_eq!(shell.rhs(&line), Some(quoted.as_str()));
+    }
+
+    #[test]
+    fn nushell_quote_rejects_embedded_newline() {
+        let shell = Nushell;
+        assert!(shell.quote("one\ntwo").is_err());
+    }
+
+    #[test]
+    fn nushell_non_path_round_trips_through_format_set() {
+        let shell = Nushell;
+        let line = shell.format_set("FOO", "\"bar\"");
+        assert_eq!(shell.parse_var(&line), Some("FOO".to_string()));
+        assert_eq!(shell.rhs(&line), Some("\"bar\""));
+    }
+
+    #[test]
+    fn powershell_quote_doubles_embedded_single_quotes() {
+        let shell = PowerShell;
+        let payload = r#"'; rm -rf ~; echo "$(whoami)" `id` \ '"#;
+        let quoted = shell.quote(payload).unwrap();
+
+        assert_eq!(quoted, r#"'''; rm -rf ~; echo "$(whoami)" `id` \ '''"#);
+
+        let line = shell.format_set("DUMMY", &quoted);
+        assert_eq!(shell.rhs(&line), Some(quoted.as_str()));
+    }
+
+    #[test]
+    fn powershell_quote_rejects_embedded_newline() {
+        let shell = PowerShell;
+        assert!(shell.quote("one\ntwo").is_err());
+    }
+
+    #[test]
+    fn powershell_append_chains_off_rhs_without_losing_segments() {
+        let shell = PowerShell;
+        let first = shell.format_append("PATH", "/a", &shell.variable_reference("PATH"));
+        let existing = shell.rhs(&first).unwrap();
+        let second = shell.format_append("PATH", "/b", existing);
+
+        assert_eq!(shell.parse_var(&second), Some("PATH".to_string()));
+        let rhs = shell.rhs(&second).unwrap();
+        assert!(rhs.contains("/a"));
+        assert!(rhs.contains("/b"));
+    }
+}
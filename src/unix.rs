@@ -0,0 +1,407 @@
+//! Unix backend: variables are persisted to a managed file (one per shell,
+//! e.g. `~/.env_perm_env.sh` for POSIX shells) which the shell's rc file is
+//! made to source, guarding against writing duplicate lines.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use boolinator::Boolinator;
+
+use crate::shell::{self, reject_newline, Shell};
+
+/// Sets an environment variable, overwriting any existing managed entry.
+/// `value` is quoted as a literal unless `raw` is set, in which case it's
+/// spliced in verbatim (e.g. so it can still expand `$HOME` on sourcing);
+/// either way an embedded newline is rejected, since a raw value containing
+/// one would otherwise let a caller splice extra, independently-sourced
+/// lines into the managed file.
+pub(crate) fn set(var: &str, value: &str, raw: bool) -> io::Result<()> {
+    let shell = detect_shell()?;
+    let profile = find_profile(&*shell)?;
+    ensure_sourced(&*shell, &profile)?;
+
+    let stored = if raw {
+        reject_newline(value)?;
+        value.to_string()
+    } else {
+        shell.quote(value)?
+    };
+
+    let managed = managed_env_path(&*shell)?;
+    let mut entries = read_entries(&*shell, &managed)?;
+    entries.retain(|(name, _)| name != var);
+    entries.push((var.to_string(), shell.format_set(var, &stored)));
+    write_entries(&managed, &entries)
+}
+
+/// Appends a value to an environment variable, e.g. PATH.
+/// A no-op if `value` is already one of its existing segments.
+pub(crate) fn append(var: &str, value: &str) -> io::Result<()> {
+    reject_newline(value)?;
+
+    let shell = detect_shell()?;
+    let profile = find_profile(&*shell)?;
+    ensure_sourced(&*shell, &profile)?;
+
+    let managed = managed_env_path(&*shell)?;
+    let mut entries = read_entries(&*shell, &managed)?;
+
+    if shell.append_is_composable(var) {
+        let line = shell.format_append(var, value, "");
+        let already_appended = entries
+            .iter()
+            .any(|(name, existing)| name == var && existing == &line);
+        if already_appended {
+            return Ok(());
+        }
+        entries.push((var.to_string(), line));
+        return write_entries(&managed, &entries);
+    }
+
+    let existing = entries
+        .iter()
+        .find(|(name, _)| name == var)
+        .and_then(|(_, line)| shell.rhs(line))
+        .map(str::to_string)
+        .unwrap_or_else(|| shell.variable_reference(var));
+
+    if existing
+        .split([':', ' ', '(', ')'])
+        .any(|segment| segment.trim_matches(['\'', '"']) == value)
+    {
+        return Ok(());
+    }
+
+    let line = shell.format_append(var, value, &existing);
+    entries.retain(|(name, _)| name != var);
+    entries.push((var.to_string(), line));
+    write_entries(&managed, &entries)
+}
+
+/// Removes any managed entry for `var`, leaving the rest of the file intact.
+pub(crate) fn unset(var: &str) -> io::Result<()> {
+    let shell = detect_shell()?;
+    let managed = managed_env_path(&*shell)?;
+    let mut entries = read_entries(&*shell, &managed)?;
+    entries.retain(|(name, _)| name != var);
+    write_entries(&managed, &entries)
+}
+
+/// Path to the managed file holding every variable `env_perm` sets for
+/// the detected shell.
+fn managed_env_path(shell: &dyn Shell) -> io::Result<PathBuf> {
+    dirs::home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))
+        .map(|mut home| {
+            home.push(shell.managed_file_name());
+            home
+        })
+}
+
+/// Reads the managed file's entries back into `(name, line)` pairs,
+/// preserving their order and each line exactly as it was written. A
+/// missing file is treated as empty.
+///
+/// Lines are kept verbatim rather than reduced to a `(name, value)` pair,
+/// so an entry produced by `format_append` (which isn't always
+/// representable as `format_set(name, value)`, e.g. fish's
+/// `fish_add_path`) survives being read back and rewritten unchanged.
+fn read_entries(shell: &dyn Shell, path: &Path) -> io::Result<Vec<(String, String)>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter_map(|line| Some((shell.parse_var(line)?, line.to_string())))
+            .collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Tmp path to write to before renaming into `path`. Appends `.tmp` to the
+/// full file name rather than using `Path::with_extension`, which strips
+/// only the last extension: `.env_perm_env.sh` and `.env_perm_env.fish`
+/// would otherwise both collapse to `.env_perm_env.tmp`, so two shells
+/// writing concurrently could race on the same temp file.
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let tmp_name = match path.file_name() {
+        Some(name) => {
+            let mut tmp_name = name.to_os_string();
+            tmp_name.push(".tmp");
+            tmp_name
+        }
+        None => "env_perm.tmp".into(),
+    };
+    path.with_file_name(tmp_name)
+}
+
+/// Rewrites the managed file from scratch, writing to a temp file and
+/// renaming it into place so a crash mid-write can't corrupt it.
+fn write_entries(path: &Path, entries: &[(String, String)]) -> io::Result<()> {
+    let tmp_path = tmp_path_for(path);
+
+    let mut tmp_file = File::create(&tmp_path)?;
+    writeln!(tmp_file, "# Managed by env_perm. Do not edit by hand.")?;
+    for (_, line) in entries {
+        writeln!(tmp_file, "{}", line)?;
+    }
+    tmp_file.flush()?;
+
+    fs::rename(tmp_path, path)
+}
+
+/// Makes sure `profile` contains the guarded source line exactly once,
+/// appending it if it's missing.
+fn ensure_sourced(shell: &dyn Shell, profile: &Path) -> io::Result<()> {
+    let guard = shell.source_line();
+
+    let already_sourced = match fs::read_to_string(profile) {
+        Ok(contents) => contents.lines().any(|line| line == guard),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+        Err(err) => return Err(err),
+    };
+
+    if already_sourced {
+        return Ok(());
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(profile)?;
+    writeln!(file, "\n{}", guard)?;
+    file.flush()
+}
+
+/// Detects the user's shell from `$SHELL` and picks its [`Shell`] impl.
+fn detect_shell() -> io::Result<Box<dyn Shell>> {
+    let shell_bin = env::var("SHELL").expect("SHELL environment variable was not found");
+    let shell_bin = shell_bin
+        .split('/')
+        .next_back()
+        .expect("Unable to parse shell path in environment variables");
+
+    shell::detect(shell_bin).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Unable to match shell_bin with a supported shell",
+        )
+    })
+}
+
+/// Finds (or creates) the rc file to put this shell's guarded source line
+/// in, verifying `$HOME` is writable first.
+fn find_profile(shell: &dyn Shell) -> io::Result<PathBuf> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))?;
+
+    let home_str = home.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Failed to coerce Home directory as a valid Path",
+        )
+    })?;
+
+    let md = fs::metadata(home_str).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Path to profile was invalid, or was not found",
+        )
+    })?;
+
+    (!md.permissions().readonly()).as_result(
+        (),
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "Unable to write to home directory, cannot export env var",
+        ),
+    )?;
+
+    let config_files = shell.config_files();
+    for candidate in config_files {
+        let mut path = home.clone();
+        path.push(candidate);
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+
+    let mut path = home;
+    if let Some(first) = config_files.first() {
+        path.push(first);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        return Ok(path);
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "No shell profiles were found",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // `set`/`append`/`unset` all resolve `$HOME` and `$SHELL` from the
+    // process environment, so tests that point them at a scratch directory
+    // can't run concurrently with each other.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Points `$HOME`/`$SHELL` at a fresh scratch directory for the
+    /// duration of `body`, then tears it down.
+    fn with_temp_home(shell_bin: &str, name: &str, body: impl FnOnce(&Path)) {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let home = env::temp_dir().join(format!("env_perm_test_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+
+        env::set_var("HOME", &home);
+        env::set_var("SHELL", format!("/usr/bin/{}", shell_bin));
+
+        body(&home);
+
+        fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn set_replaces_the_existing_entry_instead_of_appending() {
+        with_temp_home("bash", "set_replaces", |home| {
+            set("DUMMY", "one", false).unwrap();
+            set("DUMMY", "two", false).unwrap();
+
+            let contents = fs::read_to_string(home.join(".env_perm_env.sh")).unwrap();
+            let dummy_lines: Vec<_> = contents.lines().filter(|l| l.contains("DUMMY")).collect();
+
+            assert_eq!(dummy_lines, vec!["export DUMMY='two'"]);
+        });
+    }
+
+    #[test]
+    fn write_writes_through_a_tmp_file_and_renames_into_place() {
+        with_temp_home("bash", "atomic_write", |home| {
+            set("DUMMY", "one", false).unwrap();
+
+            let managed = home.join(".env_perm_env.sh");
+            assert!(managed.exists());
+            assert!(!home.join(".env_perm_env.sh.tmp").exists());
+        });
+    }
+
+    #[test]
+    fn tmp_path_stays_distinct_across_shells_sharing_a_stem() {
+        let home = Path::new("/home/user");
+        let sh = tmp_path_for(&home.join(".env_perm_env.sh"));
+        let fish = tmp_path_for(&home.join(".env_perm_env.fish"));
+        let nu = tmp_path_for(&home.join(".env_perm_env.nu"));
+        let ps1 = tmp_path_for(&home.join(".env_perm_env.ps1"));
+
+        assert_eq!(sh, home.join(".env_perm_env.sh.tmp"));
+        assert_eq!(fish, home.join(".env_perm_env.fish.tmp"));
+        assert_eq!(nu, home.join(".env_perm_env.nu.tmp"));
+        assert_eq!(ps1, home.join(".env_perm_env.ps1.tmp"));
+
+        let all = [&sh, &fish, &nu, &ps1];
+        for (i, a) in all.iter().enumerate() {
+            for (j, b) in all.iter().enumerate() {
+                assert!(i == j || a != b, "{:?} collided with {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn fish_path_appends_accumulate_instead_of_clobbering() {
+        with_temp_home("fish", "fish_path", |home| {
+            append("PATH", "/a").unwrap();
+            append("PATH", "/b").unwrap();
+
+            let contents = fs::read_to_string(home.join(".env_perm_env.fish")).unwrap();
+            assert!(contents.contains("fish_add_path /a"));
+            assert!(contents.contains("fish_add_path /b"));
+        });
+    }
+
+    #[test]
+    fn powershell_path_appends_accumulate_instead_of_clobbering() {
+        with_temp_home("pwsh", "pwsh_path", |home| {
+            append("PATH", "/a").unwrap();
+            append("PATH", "/b").unwrap();
+
+            let contents = fs::read_to_string(home.join(".env_perm_env.ps1")).unwrap();
+            assert!(contents.contains("/a"));
+            assert!(contents.contains("/b"));
+        });
+    }
+
+    #[test]
+    fn unset_removes_only_the_matching_entry() {
+        with_temp_home("bash", "unset_one", |home| {
+            set("DUMMY", "one", false).unwrap();
+            set("OTHER", "two", false).unwrap();
+            unset("DUMMY").unwrap();
+
+            let contents = fs::read_to_string(home.join(".env_perm_env.sh")).unwrap();
+            assert!(!contents.contains("DUMMY"));
+            assert!(contents.contains("OTHER"));
+        });
+    }
+
+    #[test]
+    fn set_quotes_a_malicious_value_so_it_cant_inject_on_source() {
+        with_temp_home("bash", "set_quotes_malicious", |home| {
+            let payload = r#"'; rm -rf ~; echo "$(whoami)" `id` '"#;
+            set("DUMMY", payload, false).unwrap();
+
+            let contents = fs::read_to_string(home.join(".env_perm_env.sh")).unwrap();
+            let line = contents
+                .lines()
+                .find(|l| l.contains("DUMMY"))
+                .expect("DUMMY entry");
+
+            // The whole payload is wrapped in a single-quoted literal, with
+            // any embedded `'` escaped via the `'\''` idiom, so none of the
+            // injected shell syntax is left unquoted for `sh` to interpret.
+            assert_eq!(
+                line,
+                r#"export DUMMY=''\''; rm -rf ~; echo "$(whoami)" `id` '\'''"#
+            );
+        });
+    }
+
+    #[test]
+    fn set_raw_splices_the_value_verbatim_for_expansion() {
+        with_temp_home("bash", "set_raw_verbatim", |home| {
+            set("JAVA_HOME", "$HOME/.sdkman/candidates/java/current", true).unwrap();
+
+            let contents = fs::read_to_string(home.join(".env_perm_env.sh")).unwrap();
+            assert!(contents.contains("export JAVA_HOME=$HOME/.sdkman/candidates/java/current"));
+        });
+    }
+
+    #[test]
+    fn set_raw_rejects_a_newline_instead_of_splicing_extra_lines() {
+        with_temp_home("bash", "set_raw_rejects_newline", |home| {
+            let err = set("DUMMY", "x\nexport PWNED=1", true).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            // The managed file should not have been written: a newline in a raw value
+            // would otherwise appear as an extra, independently-sourced
+            // managed-file line that executes on shell startup.
+            assert!(!home.join(".env_perm_env.sh").exists());
+        });
+    }
+
+    #[test]
+    fn append_rejects_a_newline_instead_of_splicing_extra_lines() {
+        with_temp_home("bash", "append_rejects_newline", |home| {
+            let err = append("PATH", "/a\nexport PWNED=1").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert!(!home.join(".env_perm_env.sh").exists());
+        });
+    }
+}
@@ -0,0 +1,103 @@
+//! Windows backend: variables are persisted straight to the `Environment`
+//! key under `HKEY_CURRENT_USER`, and already-running processes are told
+//! about the change via a broadcast `WM_SETTINGCHANGE` message.
+
+use std::io;
+
+use winreg::enums::{RegType, HKEY_CURRENT_USER, KEY_READ, KEY_WRITE};
+use winreg::types::ToRegValue;
+use winreg::RegKey;
+
+const ENVIRONMENT_KEY: &str = "Environment";
+
+/// Sets an environment variable in the user's registry hive, overwriting
+/// any existing value. Written as `REG_EXPAND_SZ` for `PATH`, or for any
+/// variable whose existing value is already `REG_EXPAND_SZ`, so references
+/// like `%JAVA_HOME%` keep expanding; everything else is plain `REG_SZ`.
+pub(crate) fn set(var: &str, value: &str) -> io::Result<()> {
+    let env = user_environment_key(KEY_READ | KEY_WRITE)?;
+    write_value(&env, var, value)?;
+    broadcast_environment_change();
+    Ok(())
+}
+
+/// Appends a value to a `;`-delimited registry value, e.g. PATH.
+/// A no-op if `value` is already one of its segments.
+pub(crate) fn append(var: &str, value: &str) -> io::Result<()> {
+    let env = user_environment_key(KEY_READ | KEY_WRITE)?;
+
+    let existing: String = env.get_value(var).unwrap_or_default();
+    let mut segments: Vec<&str> = existing.split(';').filter(|s| !s.is_empty()).collect();
+
+    if segments.contains(&value) {
+        return Ok(());
+    }
+    segments.push(value);
+
+    write_value(&env, var, &segments.join(";"))?;
+    broadcast_environment_change();
+    Ok(())
+}
+
+/// Writes `value` for `var`, preserving `REG_EXPAND_SZ` for `PATH` and for
+/// any variable that already has that type, and falling back to
+/// `set_value`'s plain `REG_SZ` otherwise.
+fn write_value(env: &RegKey, var: &str, value: &str) -> io::Result<()> {
+    let needs_expand_sz = var == "PATH"
+        || env
+            .get_raw_value(var)
+            .map(|raw| raw.vtype == RegType::REG_EXPAND_SZ)
+            .unwrap_or(false);
+
+    if needs_expand_sz {
+        let mut reg_value = value.to_reg_value();
+        reg_value.vtype = RegType::REG_EXPAND_SZ;
+        env.set_raw_value(var, &reg_value)
+    } else {
+        env.set_value(var, &value)
+    }
+}
+
+/// Removes a variable from the user's registry hive, if present.
+pub(crate) fn unset(var: &str) -> io::Result<()> {
+    let env = user_environment_key(KEY_WRITE)?;
+    match env.delete_value(var) {
+        Ok(()) => {
+            broadcast_environment_change();
+            Ok(())
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+fn user_environment_key(access: u32) -> io::Result<RegKey> {
+    RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(ENVIRONMENT_KEY, access)
+}
+
+/// Tells already-running processes (like Explorer) that the environment
+/// changed, the same way the Control Panel does after editing variables.
+fn broadcast_environment_change() {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+
+    use winapi::um::winuser::{SendMessageTimeoutW, HWND_BROADCAST, SMTO_ABORTIFHUNG, WM_SETTINGCHANGE};
+
+    let param: Vec<u16> = OsStr::new(ENVIRONMENT_KEY)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0,
+            param.as_ptr() as isize,
+            SMTO_ABORTIFHUNG,
+            5000,
+            ptr::null_mut(),
+        );
+    }
+}